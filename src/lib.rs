@@ -23,16 +23,25 @@
 //!
 //! The wrapper uses v4l2 (e.g. `v4l2_ioctl()` instead of `ioctl()`) until feature `no_wrapper` is
 //! enabled. The feature can be useful when it's desirable to avoid dependence on *libv4l2*.
+//!
+//! Enabling feature `rgb` adds `Frame::to_rgb()`, converting a captured frame into a packed
+//! RGB24 buffer for formats that have a converter (currently `YUYV`).
 
 extern crate libc;
 
 mod v4l2;
 
+/// Frame pixel format conversion (`Frame::to_rgb()`). Enabled by the `rgb` feature.
+#[cfg(feature = "rgb")]
+mod rgb;
+
 use std::convert::From;
 use std::ops::Deref;
 use std::os::unix::io::RawFd;
-use std::slice;
+use std::{ptr, slice};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use std::{io, fmt, str, result};
 
 use v4l2::MappedRegion;
@@ -51,7 +60,11 @@ pub enum Error {
     /// Unsupported format of pixel.
     BadFormat,
     /// Unsupported field.
-    BadField
+    BadField,
+    /// Value out of the range accepted by a control.
+    BadValue,
+    /// Frame data larger than the buffer size negotiated by `start()`.
+    BadLength
 }
 
 impl From<io::Error> for Error {
@@ -75,6 +88,28 @@ pub enum Field {
     InterplacedBT
 }
 
+/// Whether a `Camera` captures frames from a device or pushes them into one (e.g. a
+/// v4l2loopback virtual device).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Capture,
+    Output
+}
+
+/// I/O method used to exchange buffers with the driver, following GStreamer's io-mode
+/// concept.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IoMode {
+    /// Probe `VIDIOC_QUERYCAP` and pick the best method the device supports.
+    Auto,
+    /// Memory-mapped buffers (the default on most drivers).
+    Mmap,
+    /// User-space buffers passed to the driver by address.
+    UserPtr,
+    /// Plain blocking `read()`/`write()`, no buffer queue.
+    ReadWrite
+}
+
 pub struct Config<'a> {
     /// The mix of numerator and denominator. v4l2 uses frame intervals instead of frame rates.
     /// Default is `(1, 10)`.
@@ -90,7 +125,13 @@ pub struct Config<'a> {
     pub field: Field,
     /// Number of buffers in the queue of camera.
     /// Default is `2`.
-    pub nbuffers: u32
+    pub nbuffers: u32,
+    /// Whether to capture from the device or output/write into it.
+    /// Default is `Direction::Capture`.
+    pub dir: Direction,
+    /// I/O method used to exchange buffers with the driver.
+    /// Default is `IoMode::Auto`.
+    pub io: IoMode
 }
 
 impl<'a> Default for Config<'a> {
@@ -100,11 +141,34 @@ impl<'a> Default for Config<'a> {
             resolution: (640, 480),
             format: b"YUYV",
             field: Field::None,
-            nbuffers: 2
+            nbuffers: 2,
+            dir: Direction::Capture,
+            io: IoMode::Auto
         }
     }
 }
 
+/// Detailed info about the device itself, from `VIDIOC_QUERYCAP`.
+#[derive(Debug)]
+pub struct Capability {
+    /// Name of the driver (e.g. `"uvcvideo"`).
+    pub driver: String,
+    /// Name of the card/device.
+    pub card: String,
+    /// Location of the device in the system (e.g. a PCI or USB path).
+    pub bus_info: String,
+    /// (major, minor, patch) version of the driver.
+    pub version: (u8, u8, u8),
+    /// Whether the device can capture video.
+    pub video_capture: bool,
+    /// Whether the device can output video (e.g. a loopback sink).
+    pub video_output: bool,
+    /// Whether the device supports the streaming (`MMAP`/`USERPTR`) I/O methods.
+    pub streaming: bool,
+    /// Whether the device supports the `read()`/`write()` I/O method.
+    pub readwrite: bool
+}
+
 pub struct FormatInfo {
     /// FourCC of format (e.g. `b"H264"`).
     pub format: [u8; 4],
@@ -154,6 +218,40 @@ impl fmt::Debug for FormatInfo {
     }
 }
 
+/// Kind of a physical input, from `VIDIOC_ENUMINPUT`.
+#[derive(Debug, PartialEq)]
+pub enum InputType {
+    Tuner,
+    Camera,
+    /// Any input type not listed above, carrying the raw `v4l2_input.type` value.
+    Unknown(u32)
+}
+
+impl InputType {
+    fn from_raw(typ: u32) -> InputType {
+        match typ {
+            v4l2::INPUT_TYPE_TUNER => InputType::Tuner,
+            v4l2::INPUT_TYPE_CAMERA => InputType::Camera,
+            other => InputType::Unknown(other)
+        }
+    }
+}
+
+/// Detailed info about a single physical input (composite, S-Video, tuner channel, etc.).
+#[derive(Debug)]
+pub struct InputInfo {
+    /// Index used with `set_input()`.
+    pub index: u32,
+    /// Human-readable name of the input.
+    pub name: String,
+    /// Kind of the input.
+    pub typ: InputType,
+    /// Raw `v4l2_input.status` (e.g. `V4L2_IN_ST_NO_SIGNAL`).
+    pub status: u32,
+    /// Bitmask of video standards (e.g. PAL/NTSC) supported on this input.
+    pub std: u64
+}
+
 pub enum ResolutionInfo {
     Discretes(Vec<(u32, u32)>),
     Stepwise {
@@ -210,29 +308,149 @@ impl fmt::Debug for IntervalInfo {
     }
 }
 
+/// Kind of value a control holds.
+#[derive(Debug, PartialEq)]
+pub enum CtrlType {
+    Integer,
+    Boolean,
+    Menu,
+    Button,
+    /// Any control type not listed above, carrying the raw `v4l2_ctrl_type` value.
+    Unknown(u32)
+}
+
+impl CtrlType {
+    fn from_raw(typ: u32) -> CtrlType {
+        match typ {
+            v4l2::CTRL_TYPE_INTEGER => CtrlType::Integer,
+            v4l2::CTRL_TYPE_BOOLEAN => CtrlType::Boolean,
+            v4l2::CTRL_TYPE_MENU => CtrlType::Menu,
+            v4l2::CTRL_TYPE_BUTTON => CtrlType::Button,
+            other => CtrlType::Unknown(other)
+        }
+    }
+}
+
+/// One item of a menu-type control.
+#[derive(Debug)]
+pub struct MenuItem {
+    pub index: u32,
+    pub name: String
+}
+
+/// Detailed info about a single device control (`VIDIOC_QUERYCTRL`).
+#[derive(Debug)]
+pub struct CtrlInfo {
+    /// Control id (e.g. `V4L2_CID_BRIGHTNESS`).
+    pub id: u32,
+    /// Human-readable name of the control.
+    pub name: String,
+    /// Kind of value the control holds.
+    pub typ: CtrlType,
+    /// Minimum value.
+    pub minimum: i32,
+    /// Maximum value.
+    pub maximum: i32,
+    /// Step between two consecutive values.
+    pub step: i32,
+    /// Value assumed by a newly opened device.
+    pub default_value: i32,
+    /// Items of a `Menu` control, `None` for any other type.
+    pub menu_items: Option<Vec<MenuItem>>
+}
+
+fn cstr(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(match bytes.iter().position(|&c| c == 0) {
+        Some(x) => &bytes[..x],
+        None => bytes
+    }).into_owned()
+}
+
+/// Page-aligned heap buffer for `V4L2_MEMORY_USERPTR` buffers: some drivers require userptrs
+/// passed to `VIDIOC_QBUF` to be aligned to the page size, so a plain `Vec<u8>` (which only
+/// guarantees `align_of::<u8>()`) isn't sufficient. Freed via `libc::free` on drop.
+struct AlignedBuffer(*mut u8);
+
+impl AlignedBuffer {
+    fn new(len: usize) -> io::Result<AlignedBuffer> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let mut ptr: *mut libc::c_void = ptr::null_mut();
+
+        let ret = unsafe { libc::posix_memalign(&mut ptr, page_size, len) };
+
+        if ret != 0 {
+            return Err(io::Error::from_raw_os_error(ret));
+        }
+
+        unsafe { ptr::write_bytes(ptr as *mut u8, 0, len); }
+
+        Ok(AlignedBuffer(ptr as *mut u8))
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { libc::free(self.0 as *mut libc::c_void); }
+    }
+}
+
+/// Backing storage for a streaming buffer. `Mmap` is unmapped on drop by `MappedRegion`
+/// itself; `UserPtr` is a page-aligned heap allocation owned by this process, freed via
+/// `libc::free` on drop. Keeping them as distinct variants (rather than handing USERPTR
+/// memory to `MappedRegion`, which always `munmap()`s) avoids unmapping memory that was
+/// never mapped.
+enum Region {
+    Mmap(MappedRegion),
+    UserPtr(AlignedBuffer)
+}
+
+impl Region {
+    fn as_ptr(&self) -> *mut u8 {
+        match *self {
+            Region::Mmap(ref region) => region.ptr,
+            Region::UserPtr(ref buf) => buf.0
+        }
+    }
+}
+
+enum FrameData {
+    /// Backed by a queued buffer (`Mmap`/`UserPtr` I/O); requeued on drop.
+    Queued {
+        region: Arc<Region>,
+        fd: RawFd,
+        buffer: v4l2::Buffer
+    },
+    /// Backed by a plain heap buffer filled by `read()` (`ReadWrite` I/O).
+    Owned(Vec<u8>)
+}
+
 pub struct Frame {
     /// Width and height of the frame.
     pub resolution: (u32, u32),
     /// FourCC of the format.
     pub format: [u8; 4],
 
-    region: Arc<MappedRegion>,
     length: u32,
-    fd: RawFd,
-    buffer: v4l2::Buffer
+    data: FrameData
 }
 
 impl Deref for Frame {
     type Target = [u8];
 
     fn deref(&self) -> &[u8] {
-        unsafe { slice::from_raw_parts(self.region.ptr, self.length as usize) }
+        match self.data {
+            FrameData::Queued { ref region, .. } =>
+                unsafe { slice::from_raw_parts(region.as_ptr(), self.length as usize) },
+            FrameData::Owned(ref buf) => &buf[..self.length as usize]
+        }
     }
 }
 
 impl Drop for Frame {
     fn drop(&mut self) {
-        let _ = v4l2::xioctl(self.fd, v4l2::VIDIOC_QBUF, &mut self.buffer);
+        if let FrameData::Queued { fd, ref mut buffer, .. } = self.data {
+            let _ = v4l2::xioctl(fd, v4l2::VIDIOC_QBUF, buffer);
+        }
     }
 }
 
@@ -246,9 +464,16 @@ enum State {
 pub struct Camera {
     fd: RawFd,
     state: State,
+    dir: Direction,
+    io: IoMode,
     resolution: (u32, u32),
     format: [u8; 4],
-    buffers: Vec<Arc<MappedRegion>>
+    buffers: Vec<Arc<Region>>,
+    buf_length: u32,
+    /// Whether the driver supports `poll()` on this fd, probed once in `start()` and
+    /// downgraded if `capture_timeout()` later finds polling has stopped working. When
+    /// `false` the fd is left blocking and `capture_timeout()` degrades to `capture()`.
+    poll_ok: AtomicBool
 }
 
 impl Camera {
@@ -256,9 +481,13 @@ impl Camera {
         Ok(Camera {
             fd: try!(v4l2::open(device)),
             state: State::Idle,
+            dir: Direction::Capture,
+            io: IoMode::Mmap,
             resolution: (0, 0),
             format: [0; 4],
-            buffers: vec![]
+            buffers: vec![],
+            buf_length: 0,
+            poll_ok: AtomicBool::new(true)
         })
     }
 
@@ -357,6 +586,157 @@ impl Camera {
         }
     }
 
+    /// Get detailed info about the available inputs (composite, S-Video, tuner channels, etc.).
+    pub fn inputs(&self) -> io::Result<Vec<InputInfo>> {
+        let mut inputs = vec![];
+        let mut inp = v4l2::Input::new();
+
+        while try!(v4l2::xioctl_valid(self.fd, v4l2::VIDIOC_ENUMINPUT, &mut inp)) {
+            inputs.push(InputInfo {
+                index: inp.index,
+                name: cstr(&inp.name),
+                typ: InputType::from_raw(inp.typ),
+                status: inp.status,
+                std: inp.std
+            });
+
+            inp.index += 1;
+        }
+
+        Ok(inputs)
+    }
+
+    /// Get the index of the currently selected input.
+    pub fn get_input(&self) -> io::Result<u32> {
+        let mut index = 0u32;
+
+        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_G_INPUT, &mut index));
+
+        Ok(index)
+    }
+
+    /// Select the input to capture from. Must be called before `start()`.
+    pub fn set_input(&self, mut index: u32) -> io::Result<()> {
+        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_S_INPUT, &mut index));
+
+        Ok(())
+    }
+
+    /// Get detailed info about the device, including the capabilities (video capture/output,
+    /// streaming, read/write) it was opened with. On multi-function devices, this reflects the
+    /// capabilities of the opened video node (`device_caps`), not the whole device.
+    pub fn capabilities(&self) -> io::Result<Capability> {
+        let mut raw = v4l2::Capability::new();
+        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_QUERYCAP, &mut raw));
+
+        let caps = if raw.capabilities & v4l2::CAP_DEVICE_CAPS != 0 {
+            raw.device_caps
+        } else {
+            raw.capabilities
+        };
+
+        Ok(Capability {
+            driver: cstr(&raw.driver),
+            card: cstr(&raw.card),
+            bus_info: cstr(&raw.bus_info),
+            version: (
+                (raw.version >> 16 & 0xff) as u8,
+                (raw.version >> 8 & 0xff) as u8,
+                (raw.version & 0xff) as u8
+            ),
+            video_capture: caps & v4l2::CAP_VIDEO_CAPTURE != 0,
+            video_output: caps & v4l2::CAP_VIDEO_OUTPUT != 0,
+            streaming: caps & v4l2::CAP_STREAMING != 0,
+            readwrite: caps & v4l2::CAP_READWRITE != 0
+        })
+    }
+
+    /// Get detailed info about the available controls (brightness, contrast, exposure, etc.),
+    /// including extended and private ones. Works both before and during streaming.
+    pub fn controls(&self) -> Result<Vec<CtrlInfo>> {
+        let mut infos = vec![];
+        let mut id = v4l2::CID_BASE | v4l2::CTRL_FLAG_NEXT_CTRL;
+
+        loop {
+            let mut query = v4l2::Queryctrl::new(id);
+
+            if !try!(v4l2::xioctl_valid(self.fd, v4l2::VIDIOC_QUERYCTRL, &mut query)) {
+                break;
+            }
+
+            if query.flags & v4l2::CTRL_FLAG_DISABLED == 0 {
+                infos.push(try!(self.ctrl_info(&query)));
+            }
+
+            id = query.id | v4l2::CTRL_FLAG_NEXT_CTRL;
+        }
+
+        Ok(infos)
+    }
+
+    /// Get the current value of a control.
+    pub fn get_control(&self, id: u32) -> Result<i32> {
+        let mut ctrl = v4l2::Control::new(id);
+
+        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_G_CTRL, &mut ctrl));
+
+        Ok(ctrl.value)
+    }
+
+    /// Set the value of a control. Returns `Error::BadValue` if `value` is outside of the
+    /// control's queried range.
+    pub fn set_control(&self, id: u32, value: i32) -> Result<()> {
+        let mut query = v4l2::Queryctrl::new(id);
+        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_QUERYCTRL, &mut query));
+
+        if value < query.minimum || value > query.maximum {
+            return Err(Error::BadValue);
+        }
+
+        let mut ctrl = v4l2::Control::new(id);
+        ctrl.value = value;
+
+        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_S_CTRL, &mut ctrl));
+
+        Ok(())
+    }
+
+    fn ctrl_info(&self, query: &v4l2::Queryctrl) -> Result<CtrlInfo> {
+        let menu_items = if query.typ == v4l2::CTRL_TYPE_MENU {
+            Some(try!(self.menu_items(query.id, query.minimum, query.maximum)))
+        } else {
+            None
+        };
+
+        Ok(CtrlInfo {
+            id: query.id,
+            name: cstr(&query.name),
+            typ: CtrlType::from_raw(query.typ),
+            minimum: query.minimum,
+            maximum: query.maximum,
+            step: query.step,
+            default_value: query.default_value,
+            menu_items: menu_items
+        })
+    }
+
+    fn menu_items(&self, id: u32, min: i32, max: i32) -> Result<Vec<MenuItem>> {
+        let mut items = vec![];
+
+        for i in min..(max + 1) {
+            let mut menu = v4l2::Querymenu::new(id, i as u32);
+
+            if try!(v4l2::xioctl_valid(self.fd, v4l2::VIDIOC_QUERYMENU, &mut menu)) {
+                items.push(MenuItem {
+                    index: i as u32,
+                    name: cstr(&menu.name)
+                });
+            }
+        }
+
+        Ok(items)
+    }
+
     /// Start streaming.
     ///
     /// # Panics
@@ -364,13 +744,49 @@ impl Camera {
     pub fn start(&mut self, config: &Config) -> Result<()> {
         assert_eq!(self.state, State::Idle);
 
+        self.dir = config.dir;
+
         try!(self.tune_format(config.resolution, config.format, config.field));
-        try!(self.tune_stream(config.interval));
-        try!(self.alloc_buffers(config.nbuffers));
 
-        if let Err(err) = self.streamon() {
-            self.free_buffers();
-            return Err(Error::Io(err));
+        if config.dir == Direction::Capture {
+            try!(self.tune_stream(config.interval));
+        }
+
+        self.io = try!(self.resolve_io_mode(config.io));
+
+        if self.io != IoMode::ReadWrite {
+            try!(self.alloc_buffers(config.nbuffers));
+
+            if let Err(err) = self.streamon() {
+                self.free_buffers();
+                return Err(Error::Io(err));
+            }
+
+            // Probe poll() support once, now that buffers are queued and streaming, rather
+            // than per `capture_timeout()` call: only then is it safe to make the fd
+            // non-blocking for the whole streaming session, so plain `capture()` still
+            // blocks (via `dequeue()`'s poll-and-retry) without racing another thread
+            // toggling O_NONBLOCK mid-call. Probing before `VIDIOC_STREAMON` would risk a
+            // driver misreporting poll() support for a stream that isn't running yet.
+            let poll_ok = match v4l2::poll(self.fd, Some(Duration::new(0, 0))) {
+                Ok(_) => true,
+                Err(ref err) if v4l2::poll_unsupported(err) => false,
+                Err(err) => {
+                    let _ = self.streamoff();
+                    self.free_buffers();
+                    return Err(Error::Io(err));
+                }
+            };
+
+            if poll_ok {
+                if let Err(err) = v4l2::set_nonblocking(self.fd, true) {
+                    let _ = self.streamoff();
+                    self.free_buffers();
+                    return Err(Error::Io(err));
+                }
+            }
+
+            self.poll_ok = AtomicBool::new(poll_ok);
         }
 
         self.resolution = config.resolution;
@@ -388,20 +804,155 @@ impl Camera {
     /// If called w/o streaming.
     pub fn capture(&self) -> io::Result<Frame> {
         assert_eq!(self.state, State::Streaming);
+        assert_eq!(self.dir, Direction::Capture);
+
+        if self.io == IoMode::ReadWrite {
+            let mut data = vec![0u8; self.buf_length as usize];
+            let nread = try!(v4l2::read(self.fd, &mut data));
+
+            return Ok(Frame {
+                resolution: self.resolution,
+                format: self.format,
+                length: nread as u32,
+                data: FrameData::Owned(data)
+            });
+        }
 
-        let mut buf = v4l2::Buffer::new();
+        let buf = try!(self.dequeue());
 
-        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_DQBUF, &mut buf));
-        assert!(buf.index < self.buffers.len() as u32);
+        Ok(self.frame_from_buffer(buf))
+    }
+
+    /// Like `capture()`, but gives up and returns `Ok(None)` if no frame arrives within
+    /// `timeout`, instead of blocking indefinitely. With `IoMode::ReadWrite`, or if `start()`
+    /// found the driver doesn't support polling, or polling stops working partway through the
+    /// session, degrades to a plain blocking `capture()` and the timeout is not applied.
+    ///
+    /// # Panics
+    /// If called w/o streaming or on a `Direction::Output` camera.
+    pub fn capture_timeout(&self, timeout: Duration) -> io::Result<Option<Frame>> {
+        assert_eq!(self.state, State::Streaming);
+        assert_eq!(self.dir, Direction::Capture);
+
+        if self.io == IoMode::ReadWrite || !self.poll_ok.load(Ordering::Relaxed) {
+            return self.capture().map(Some);
+        }
+
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let mut buf = v4l2::Buffer::new(self.buf_type(), self.memory());
+
+            match v4l2::xioctl(self.fd, v4l2::VIDIOC_DQBUF, &mut buf) {
+                Ok(()) => return Ok(Some(self.frame_from_buffer(buf))),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {},
+                Err(err) => return Err(err)
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining == Duration::new(0, 0) {
+                return Ok(None);
+            }
+
+            match v4l2::poll(self.fd, Some(remaining)) {
+                Ok(true) => {},
+                Ok(false) => return Ok(None),
+                // The driver stopped supporting poll() mid-session (e.g. after an input
+                // switch) even though `start()`'s probe succeeded. Downgrade permanently and
+                // put the fd back in blocking mode so `capture()`'s `dequeue()` can still
+                // wait for a frame without relying on `poll()` again.
+                Err(ref err) if v4l2::poll_unsupported(err) => {
+                    try!(self.downgrade_poll());
+                    return self.capture().map(Some);
+                },
+                Err(err) => return Err(err)
+            }
+        }
+    }
+
+    /// Dequeue a filled buffer, blocking until one is ready. While `poll_ok` holds, the fd is
+    /// non-blocking and this blocks via `poll()`; if the driver turns out not to support
+    /// `poll()` after all, `downgrade_poll()` puts the fd back in blocking mode, so
+    /// `VIDIOC_DQBUF` above just blocks directly without ever hitting the `WouldBlock` branch
+    /// again.
+    fn dequeue(&self) -> io::Result<v4l2::Buffer> {
+        loop {
+            let mut buf = v4l2::Buffer::new(self.buf_type(), self.memory());
+
+            match v4l2::xioctl(self.fd, v4l2::VIDIOC_DQBUF, &mut buf) {
+                Ok(()) => return Ok(buf),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    match v4l2::poll(self.fd, None) {
+                        Ok(_) => {},
+                        Err(ref err) if v4l2::poll_unsupported(err) => try!(self.downgrade_poll()),
+                        Err(err) => return Err(err)
+                    }
+                },
+                Err(err) => return Err(err)
+            }
+        }
+    }
+
+    /// Permanently fall back to blocking I/O for the rest of the streaming session after
+    /// finding out (from a failed `poll()`) that the driver doesn't actually support it.
+    /// Guarded by a compare-and-swap so only the first caller to notice actually flips the
+    /// fd's mode, in case `capture()`/`capture_timeout()` are ever driven from more than one
+    /// thread.
+    fn downgrade_poll(&self) -> io::Result<()> {
+        if self.poll_ok.compare_and_swap(true, false, Ordering::SeqCst) {
+            try!(v4l2::set_nonblocking(self.fd, false));
+        }
+
+        Ok(())
+    }
 
-        Ok(Frame {
+    fn frame_from_buffer(&self, buffer: v4l2::Buffer) -> Frame {
+        assert!(buffer.index < self.buffers.len() as u32);
+
+        Frame {
             resolution: self.resolution,
             format: self.format,
-            region: self.buffers[buf.index as usize].clone(),
-            length: buf.bytesused,
-            fd: self.fd,
-            buffer: buf
-        })
+            length: buffer.bytesused,
+            data: FrameData::Queued {
+                region: self.buffers[buffer.index as usize].clone(),
+                fd: self.fd,
+                buffer: buffer
+            }
+        }
+    }
+
+    /// Write a frame into an output/loopback device.
+    ///
+    /// # Panics
+    /// If called w/o streaming or on a `Direction::Capture` camera.
+    pub fn write_frame(&self, data: &[u8]) -> Result<()> {
+        assert_eq!(self.state, State::Streaming);
+        assert_eq!(self.dir, Direction::Output);
+
+        if data.len() > self.buf_length as usize {
+            return Err(Error::BadLength);
+        }
+
+        if self.io == IoMode::ReadWrite {
+            try!(v4l2::write(self.fd, data));
+            return Ok(());
+        }
+
+        let mut buf = try!(self.dequeue());
+        assert!(buf.index < self.buffers.len() as u32);
+
+        let region = &self.buffers[buf.index as usize];
+
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), region.as_ptr(), data.len());
+        }
+
+        buf.bytesused = data.len() as u32;
+
+        try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_QBUF, &mut buf));
+
+        Ok(())
     }
 
     /// Stop streaming. Otherwise it's called after destructing `Camera`.
@@ -411,21 +962,23 @@ impl Camera {
     pub fn stop(&mut self) -> io::Result<()> {
         assert_eq!(self.state, State::Streaming);
 
-        try!(self.streamoff());
-        self.free_buffers();
+        if self.io != IoMode::ReadWrite {
+            try!(self.streamoff());
+            self.free_buffers();
+        }
 
         self.state = State::Aborted;
 
         Ok(())
     }
 
-    fn tune_format(&self, resolution: (u32, u32), format: &[u8], field: Field) -> Result<()> {
+    fn tune_format(&mut self, resolution: (u32, u32), format: &[u8], field: Field) -> Result<()> {
         if format.len() != 4 {
             return Err(Error::BadFormat);
         }
 
         let fourcc = FormatInfo::fourcc(format);
-        let mut fmt = v4l2::Format::new(resolution, fourcc, field as u32);
+        let mut fmt = v4l2::Format::new(resolution, fourcc, field as u32, self.buf_type());
 
         try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_S_FMT, &mut fmt));
 
@@ -441,6 +994,8 @@ impl Camera {
             return Err(Error::BadField);
         }
 
+        self.buf_length = fmt.fmt.sizeimage;
+
         Ok(())
     }
 
@@ -458,16 +1013,23 @@ impl Camera {
     }
 
     fn alloc_buffers(&mut self, nbuffers: u32) -> Result<()> {
-        let mut req = v4l2::RequestBuffers::new(nbuffers);
+        let mut req = v4l2::RequestBuffers::new(nbuffers, self.buf_type(), self.memory());
 
         try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_REQBUFS, &mut req));
 
         for i in 0..nbuffers {
-            let mut buf = v4l2::Buffer::new();
-            buf.index = i;
-            try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_QUERYBUF, &mut buf));
+            let region = if self.io == IoMode::UserPtr {
+                Region::UserPtr(try!(AlignedBuffer::new(self.buf_length as usize)))
+            } else {
+                let mut buf = v4l2::Buffer::new(self.buf_type(), self.memory());
+                buf.index = i;
+                try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_QUERYBUF, &mut buf));
+
+                self.buf_length = buf.length;
+
+                Region::Mmap(try!(v4l2::mmap(buf.length as usize, self.fd, buf.m)))
+            };
 
-            let region = try!(v4l2::mmap(buf.length as usize, self.fd, buf.m));
             self.buffers.push(Arc::new(region));
         }
 
@@ -480,24 +1042,58 @@ impl Camera {
 
     fn streamon(&self) -> io::Result<()> {
         for i in 0..self.buffers.len() {
-            let mut buf = v4l2::Buffer::new();
+            let mut buf = v4l2::Buffer::new(self.buf_type(), self.memory());
             buf.index = i as u32;
 
+            if self.io == IoMode::UserPtr {
+                buf.set_userptr(self.buffers[i].as_ptr() as usize, self.buf_length);
+            }
+
             try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_QBUF, &mut buf));
         }
 
-        let mut typ = v4l2::BUF_TYPE_VIDEO_CAPTURE;
+        let mut typ = self.buf_type();
         try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_STREAMON, &mut typ));
 
         Ok(())
     }
 
     fn streamoff(&mut self) -> io::Result<()> {
-        let mut typ = v4l2::BUF_TYPE_VIDEO_CAPTURE;
+        let mut typ = self.buf_type();
         try!(v4l2::xioctl(self.fd, v4l2::VIDIOC_STREAMOFF, &mut typ));
 
         Ok(())
     }
+
+    fn buf_type(&self) -> u32 {
+        match self.dir {
+            Direction::Capture => v4l2::BUF_TYPE_VIDEO_CAPTURE,
+            Direction::Output => v4l2::BUF_TYPE_VIDEO_OUTPUT
+        }
+    }
+
+    fn memory(&self) -> u32 {
+        match self.io {
+            IoMode::UserPtr => v4l2::MEMORY_USERPTR,
+            _ => v4l2::MEMORY_MMAP
+        }
+    }
+
+    fn resolve_io_mode(&self, requested: IoMode) -> io::Result<IoMode> {
+        if requested != IoMode::Auto {
+            return Ok(requested);
+        }
+
+        let cap = try!(self.capabilities());
+
+        if cap.streaming {
+            Ok(IoMode::Mmap)
+        } else if cap.readwrite {
+            Ok(IoMode::ReadWrite)
+        } else {
+            Ok(IoMode::Mmap)
+        }
+    }
 }
 
 impl Drop for Camera {
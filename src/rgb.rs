@@ -0,0 +1,69 @@
+//! `YUYV`-to-`RGB3` conversion for `Frame`, mirroring what libv4l's convert layer offers.
+
+use {Error, Frame, Result};
+
+impl Frame {
+    /// Convert the frame into a packed `RGB3` (interleaved R, G, B) buffer.
+    ///
+    /// Returns `Error::BadFormat` if there's no converter for `self.format`.
+    pub fn to_rgb(&self) -> Result<Vec<u8>> {
+        match &self.format {
+            b"YUYV" => Ok(yuyv_to_rgb(&self[..], self.resolution)),
+            _ => Err(Error::BadFormat)
+        }
+    }
+}
+
+/// Upsamples `YUV422` (two pixels sharing one U/V pair) to RGB using the BT.601 coefficients.
+fn yuyv_to_rgb(data: &[u8], resolution: (u32, u32)) -> Vec<u8> {
+    let (width, height) = resolution;
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+
+    for pair in data.chunks(4) {
+        if pair.len() < 4 {
+            break;
+        }
+
+        let (y0, u, y1, v) = (pair[0] as f32, pair[1] as f32 - 128.0,
+            pair[2] as f32, pair[3] as f32 - 128.0);
+
+        rgb.extend_from_slice(&yuv_to_rgb_pixel(y0, u, v));
+        rgb.extend_from_slice(&yuv_to_rgb_pixel(y1, u, v));
+    }
+
+    rgb
+}
+
+fn yuv_to_rgb_pixel(y: f32, u: f32, v: f32) -> [u8; 3] {
+    [clamp(y + 1.402 * v), clamp(y - 0.344 * u - 0.714 * v), clamp(y + 1.772 * u)]
+}
+
+fn clamp(x: f32) -> u8 {
+    if x < 0.0 {
+        0
+    } else if x > 255.0 {
+        255
+    } else {
+        x as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::yuyv_to_rgb;
+
+    #[test]
+    fn converts_a_neutral_chroma_quad_to_matching_gray_pixels() {
+        // U/V at 128 carries no color, so each pixel's RGB should equal its own Y.
+        let quad = [200, 128, 50, 128];
+
+        assert_eq!(yuyv_to_rgb(&quad, (2, 1)), vec![200, 200, 200, 50, 50, 50]);
+    }
+
+    #[test]
+    fn drops_a_trailing_partial_pixel_pair() {
+        let data = [200, 128, 50, 128, 10, 20, 30];
+
+        assert_eq!(yuyv_to_rgb(&data, (2, 1)), vec![200, 200, 200, 50, 50, 50]);
+    }
+}